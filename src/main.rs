@@ -1,11 +1,12 @@
 use clap::Parser;
 use anyhow::{Result, Context, bail};
-use std::path::PathBuf;
-use dialoguer::{Select, Input};
+use std::path::{Path, PathBuf};
+use dialoguer::{Select, Input, MultiSelect, Confirm};
 use std::process::Command;
 use which::which;
 use std::collections::BTreeMap;
 use colored::Colorize;
+use serde::Deserialize;
 
 #[derive(Parser)]
 #[command(
@@ -21,105 +22,451 @@ struct Cli {
     /// Output directory for downloaded files (optional, will prompt if not provided)
     #[arg(short, long)]
     output_dir: Option<PathBuf>,
-    
+
     /// Enable verbose logging
     #[arg(short, long)]
     verbose: bool,
+
+    /// Comma-separated subtitle language codes (e.g. "en,es") to embed or download,
+    /// skipping the interactive subtitle prompt
+    #[arg(long, value_name = "LANGS")]
+    subs: Option<String>,
+
+    /// Only download a portion of the video, e.g. "00:01:00-00:02:30" (or "*START-END")
+    #[arg(long, value_name = "START-END")]
+    section: Option<String>,
+
+    /// Audio codec to convert to: best, aac, flac, mp3, m4a, opus, vorbis, wav
+    #[arg(long, value_name = "FORMAT")]
+    audio_format: Option<String>,
+
+    /// Audio quality: 0 (best) to 9 (worst) VBR, or an explicit bitrate like "128K"
+    #[arg(long, value_name = "QUALITY")]
+    audio_quality: Option<String>,
+
+    /// Comma-separated playlist item spec (e.g. "1,3,5-7") for non-interactive use
+    #[arg(long, value_name = "SPEC")]
+    playlist_items: Option<String>,
+
+    /// Force single-video download even if the URL points at a playlist
+    #[arg(long)]
+    no_playlist: bool,
+
+    /// HTTP/HTTPS/SOCKS proxy URL to route all yt-dlp requests through
+    #[arg(long, value_name = "URL")]
+    proxy: Option<String>,
+
+    /// Socket connection timeout in seconds
+    #[arg(long, value_name = "SECS")]
+    socket_timeout: Option<u32>,
+
+    /// Maximum download rate, e.g. "50K" or "4.2M"
+    #[arg(long, value_name = "RATE")]
+    limit_rate: Option<String>,
+
+    /// Number of retries for both the download and individual fragments
+    #[arg(long, value_name = "N")]
+    retries: Option<u32>,
+
+    /// Download yt-dlp automatically into the cache directory if it's not installed,
+    /// instead of just printing install instructions
+    #[arg(long)]
+    bootstrap: bool,
+}
+
+/// Network options shared by every yt-dlp invocation, so the format-listing call
+/// and the primary/fallback download calls never drift out of sync.
+#[derive(Clone, Default)]
+struct NetworkOptions {
+    proxy: Option<String>,
+    socket_timeout: Option<u32>,
+    limit_rate: Option<String>,
+    retries: Option<u32>,
+}
+
+impl From<&Cli> for NetworkOptions {
+    fn from(cli: &Cli) -> Self {
+        Self {
+            proxy: cli.proxy.clone(),
+            socket_timeout: cli.socket_timeout,
+            limit_rate: cli.limit_rate.clone(),
+            retries: cli.retries,
+        }
+    }
 }
 
+/// Build a base `yt-dlp <url>` command with the network options and the
+/// connectivity flags every invocation needs (force IPv4, skip cert checks,
+/// bypass geo-restrictions).
+fn base_command(yt_dlp: &Path, url: &str, net: &NetworkOptions) -> Command {
+    let mut command = Command::new(yt_dlp);
+    command
+        .arg(url)
+        .arg("--force-ipv4")
+        .arg("--no-check-certificates")
+        .arg("--geo-bypass");
+
+    if let Some(proxy) = &net.proxy {
+        command.arg("--proxy").arg(proxy);
+    }
+    if let Some(timeout) = net.socket_timeout {
+        command.arg("--socket-timeout").arg(timeout.to_string());
+    }
+    if let Some(rate) = &net.limit_rate {
+        command.arg("-r").arg(rate);
+    }
+    if let Some(retries) = net.retries {
+        command
+            .arg("--retries").arg(retries.to_string())
+            .arg("--fragment-retries").arg(retries.to_string());
+    }
+
+    command
+}
+
+const AUDIO_CODECS: [&str; 8] = ["best", "aac", "flac", "mp3", "m4a", "opus", "vorbis", "wav"];
+
 enum DownloadType {
     Video,
     Audio,
+    Subtitle,
+}
+
+/// Top-level JSON document produced by `yt-dlp -J`. For a playlist URL, `_type`
+/// is `"playlist"`, `formats` is absent, and the items live in `entries` instead.
+#[derive(Debug, Deserialize)]
+struct VideoInfo {
+    #[allow(dead_code)]
+    title: Option<String>,
+    #[serde(default)]
+    formats: Vec<Format>,
+    #[serde(default)]
+    subtitles: BTreeMap<String, serde_json::Value>,
+    #[serde(default)]
+    automatic_captions: BTreeMap<String, serde_json::Value>,
+    #[serde(default, rename = "_type")]
+    kind: Option<String>,
+    #[serde(default)]
+    entries: Vec<PlaylistEntry>,
+}
+
+impl VideoInfo {
+    fn is_playlist(&self) -> bool {
+        self.kind.as_deref() == Some("playlist")
+    }
 }
 
-#[derive(Clone)]
-struct FormatOption {
-    id: String,
-    format_description: String,
-    resolution: Option<u32>,
-    is_video: bool,
-    is_audio: bool,
-    extension: String,
-    filesize: Option<String>,
+/// One item of a playlist's `entries` array, listed with `--flat-playlist` so
+/// yt-dlp doesn't fully extract every entry just to render the selection menu.
+/// `url` is the per-entry URL to pass to a follow-up `fetch_video_info` call
+/// when full format/subtitle info for that entry is actually needed.
+#[derive(Debug, Deserialize)]
+struct PlaylistEntry {
+    title: Option<String>,
+    duration: Option<f64>,
+    url: Option<String>,
+}
+
+/// A single entry from `VideoInfo::formats`, mirroring the fields yt-dlp/youtube-dl
+/// emit in their JSON output.
+#[derive(Debug, Deserialize, Clone)]
+struct Format {
+    format_id: String,
+    ext: String,
+    vcodec: Option<String>,
+    acodec: Option<String>,
+    height: Option<u32>,
+    #[allow(dead_code)]
+    width: Option<u32>,
+    #[allow(dead_code)]
+    fps: Option<f64>,
+    tbr: Option<f64>,
+    #[allow(dead_code)]
+    abr: Option<f64>,
+    filesize: Option<u64>,
+    filesize_approx: Option<u64>,
+    format_note: Option<String>,
 }
 
-impl FormatOption {
-    fn parse_format_line(line: &str) -> Option<Self> {
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() < 3 {
-            return None;
+impl Format {
+    fn is_video(&self) -> bool {
+        self.vcodec.as_deref().is_some_and(|c| c != "none")
+    }
+
+    fn is_audio(&self) -> bool {
+        self.acodec.as_deref().is_some_and(|c| c != "none")
+    }
+
+    fn resolution(&self) -> Option<u32> {
+        self.height
+    }
+
+    /// Exact filesize when yt-dlp reports one, falling back to its estimate.
+    fn filesize(&self) -> Option<u64> {
+        self.filesize.or(self.filesize_approx)
+    }
+
+    fn description(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(note) = &self.format_note {
+            parts.push(note.clone());
         }
+        if let Some(tbr) = self.tbr {
+            parts.push(format!("{:.0}k", tbr));
+        }
+        parts.push(self.ext.clone());
+        parts.join(" ")
+    }
+}
 
-        let id = parts[0].to_string();
-        let extension = parts[1].to_string();
-        
-        // Parse resolution
-        let resolution = parts.iter()
-            .find(|p| p.contains('x'))
-            .and_then(|res| res.split('x').nth(1))
-            .and_then(|height| height.parse().ok());
-
-        // Determine if it's video and/or audio
-        let is_video = !line.contains("audio only");
-        let is_audio = line.contains("audio only");
-        
-        // Get filesize if available
-        let filesize = if let Some(size_pos) = parts.iter().position(|&p| p == "MiB" || p == "KiB" || p == "GiB") {
-            if size_pos > 0 {
-                // Handle both exact and approximate sizes
-                let size = parts[size_pos - 1].trim_start_matches('~').trim_start_matches("â‰ˆ");
-                Some(format!("{} {}", size, parts[size_pos]))
-            } else {
-                None
-            }
-        } else {
-            None
-        };
-
-        // Get format description (exclude size info if present)
-        let format_description = if let Some(size_pos) = parts.iter().position(|&p| p == "MiB" || p == "KiB" || p == "GiB") {
-            parts[2..size_pos-1].join(" ")
-        } else {
-            parts[2..].join(" ")
-        };
-
-        Some(Self {
-            id,
-            format_description,
-            resolution,
-            is_video,
-            is_audio,
-            extension,
-            filesize,
-        })
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.2} {}", size, UNITS[unit])
+}
+
+fn yt_dlp_cache_path() -> PathBuf {
+    let cache_dir = dirs::cache_dir().unwrap_or_else(|| PathBuf::from("."));
+    cache_dir.join("video-downloader").join(if cfg!(windows) { "yt-dlp.exe" } else { "yt-dlp" })
+}
+
+fn yt_dlp_release_asset_name() -> Result<&'static str> {
+    match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("linux", "x86_64") => Ok("yt-dlp_linux"),
+        ("linux", "aarch64") => Ok("yt-dlp_linux_aarch64"),
+        ("macos", _) => Ok("yt-dlp_macos"),
+        ("windows", _) => Ok("yt-dlp.exe"),
+        (os, arch) => bail!("No prebuilt yt-dlp binary available for {}/{}", os, arch),
     }
 }
 
-async fn ensure_yt_dlp() -> Result<()> {
-    if which("yt-dlp").is_err() {
-        println!("{}", "yt-dlp is not installed. Please install it first:".bold().red());
+/// Download the yt-dlp release binary for this host into the cache directory,
+/// mirroring `youtube_dl::downloader::download_yt_dlp`.
+async fn bootstrap_yt_dlp() -> Result<PathBuf> {
+    let dest = yt_dlp_cache_path();
+    if dest.exists() {
+        return Ok(dest);
+    }
+
+    let cache_dir = dest.parent().expect("cache path always has a parent");
+    tokio::fs::create_dir_all(cache_dir).await
+        .with_context(|| format!("Failed to create cache directory: {}", cache_dir.display()))?;
+
+    let asset = yt_dlp_release_asset_name()?;
+    let url = format!("https://github.com/yt-dlp/yt-dlp/releases/latest/download/{}", asset);
+    println!("{}", format!("Downloading yt-dlp from {}...", url).bold().cyan());
+
+    let bytes = reqwest::get(&url).await
+        .context("Failed to download yt-dlp")?
+        .bytes().await
+        .context("Failed to read yt-dlp download")?;
+
+    tokio::fs::write(&dest, &bytes).await
+        .with_context(|| format!("Failed to write yt-dlp binary to {}", dest.display()))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut perms = tokio::fs::metadata(&dest).await?.permissions();
+        perms.set_mode(0o755);
+        tokio::fs::set_permissions(&dest, perms).await?;
+    }
+
+    println!("{}", format!("yt-dlp downloaded to {}", dest.display()).bold().green());
+    Ok(dest)
+}
+
+/// Resolve the yt-dlp binary to use, falling back to downloading one into the
+/// cache directory (with consent) if it's not already installed.
+async fn ensure_yt_dlp(bootstrap: bool) -> Result<PathBuf> {
+    if let Ok(path) = which("yt-dlp") {
+        return Ok(path);
+    }
+
+    let cached = yt_dlp_cache_path();
+    if cached.exists() {
+        return Ok(cached);
+    }
+
+    println!("{}", "yt-dlp is not installed.".bold().red());
+
+    let should_bootstrap = bootstrap || Confirm::new()
+        .with_prompt("Download yt-dlp now?")
+        .default(true)
+        .interact()?;
+
+    if !should_bootstrap {
         println!("For Ubuntu/Debian: sudo apt install yt-dlp");
         println!("For other systems, visit: https://github.com/yt-dlp/yt-dlp#installation");
         bail!("yt-dlp not found");
     }
-    Ok(())
+
+    bootstrap_yt_dlp().await
 }
 
-fn parse_available_formats(formats_str: &str) -> Vec<FormatOption> {
-    let mut formats = Vec::new();
-    
-    for line in formats_str.lines() {
-        // Skip header lines
-        if line.starts_with("ID") || line.starts_with("[info]") || line.trim().is_empty() {
-            continue;
-        }
-        
-        if let Some(format) = FormatOption::parse_format_line(line) {
-            formats.push(format);
-        }
+/// Fetch the video's metadata (including every available format) as structured JSON
+/// instead of scraping yt-dlp's human-readable `-F` table. Pass `no_playlist = false`
+/// so playlist URLs come back as a `_type: "playlist"` document instead of just
+/// their first entry. Pass `flat_playlist = true` to only list a playlist's entries
+/// (id/title/duration/url) without extracting each one's formats and subtitles —
+/// fetch those separately, per entry, only for the entry actually needed.
+fn fetch_video_info(yt_dlp: &Path, url: &str, no_playlist: bool, flat_playlist: bool, net: &NetworkOptions) -> Result<VideoInfo> {
+    let mut list_info = base_command(yt_dlp, url, net);
+    list_info.arg("-J");
+    if no_playlist {
+        list_info.arg("--no-playlist");
+    }
+    if flat_playlist {
+        list_info.arg("--flat-playlist");
+    }
+    let output = list_info.output().context("Failed to fetch video info")?;
+
+    if !output.status.success() {
+        bail!(
+            "yt-dlp failed to fetch video info: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    serde_json::from_slice(&output.stdout).context("Failed to parse yt-dlp JSON output")
+}
+
+/// Prompt for subtitle languages, listing manual subtitles and auto-generated
+/// captions as separate entries since yt-dlp tracks them separately too.
+/// Returns an empty vec if there's nothing to choose from or the user picks none.
+fn select_subtitle_langs(
+    subtitles: &BTreeMap<String, serde_json::Value>,
+    automatic_captions: &BTreeMap<String, serde_json::Value>,
+    prompt: &str,
+) -> Result<Vec<String>> {
+    let mut items = Vec::new();
+    let mut langs = Vec::new();
+
+    for lang in subtitles.keys() {
+        items.push(lang.clone());
+        langs.push(lang.clone());
+    }
+    for lang in automatic_captions.keys() {
+        items.push(format!("{} (auto-generated)", lang));
+        langs.push(lang.clone());
+    }
+
+    if items.is_empty() {
+        println!("{}", "No subtitles available for this video.".yellow());
+        return Ok(Vec::new());
+    }
+
+    let selected = MultiSelect::new()
+        .with_prompt(prompt)
+        .items(&items)
+        .interact()?;
+
+    Ok(selected.into_iter().map(|i| langs[i].clone()).collect())
+}
+
+/// Prompt for which playlist entries to download, returning a yt-dlp
+/// `--playlist-items` spec (e.g. "1,3,5-7") covering the user's picks.
+fn select_playlist_items(entries: &[PlaylistEntry]) -> Result<String> {
+    let mut items: Vec<String> = entries.iter().enumerate().map(|(i, entry)| {
+        let title = entry.title.as_deref().unwrap_or("(untitled)");
+        let duration = entry.duration
+            .map(|secs| format!(" [{:.0}:{:02.0}]", (secs / 60.0).floor(), secs % 60.0))
+            .unwrap_or_default();
+        format!("{}. {}{}", i + 1, title, duration)
+    }).collect();
+    let all_idx = items.len();
+    let range_idx = all_idx + 1;
+    items.push("All entries".to_string());
+    items.push("Range (e.g. 2-5)".to_string());
+
+    let selected = MultiSelect::new()
+        .with_prompt("Select playlist entries to download")
+        .items(&items)
+        .interact()?;
+
+    if selected.contains(&all_idx) {
+        return Ok(format!("1-{}", entries.len()));
+    }
+    if selected.contains(&range_idx) {
+        let range: String = Input::new()
+            .with_prompt("Enter range (e.g. 2-5)")
+            .interact()?;
+        return Ok(range);
+    }
+    if selected.is_empty() {
+        bail!("No playlist entries selected");
+    }
+
+    Ok(selected.iter().map(|i| (i + 1).to_string()).collect::<Vec<_>>().join(","))
+}
+
+/// Parse an `HH:MM:SS` timestamp into a whole number of seconds.
+fn parse_timestamp(ts: &str) -> Result<u64> {
+    let parts: Vec<&str> = ts.split(':').collect();
+    if parts.len() != 3 {
+        bail!("Invalid timestamp '{}', expected HH:MM:SS", ts);
+    }
+
+    let hours: u64 = parts[0].parse().with_context(|| format!("Invalid timestamp component '{}'", parts[0]))?;
+    let minutes: u64 = parts[1].parse().with_context(|| format!("Invalid timestamp component '{}'", parts[1]))?;
+    let seconds: u64 = parts[2].parse().with_context(|| format!("Invalid timestamp component '{}'", parts[2]))?;
+
+    if minutes >= 60 || seconds >= 60 {
+        bail!("Invalid timestamp '{}', minutes and seconds must each be in 0-59", ts);
+    }
+
+    Ok(hours * 3600 + minutes * 60 + seconds)
+}
+
+/// Validate a `--section`/prompted range and normalize it into yt-dlp's
+/// `--download-sections` syntax, e.g. `*00:01:00-00:02:30`.
+fn parse_section(raw: &str) -> Result<String> {
+    let spec = raw.trim();
+    let without_star = spec.strip_prefix('*').unwrap_or(spec);
+    let (start, end) = without_star
+        .split_once('-')
+        .ok_or_else(|| anyhow::anyhow!("Section '{}' must be in the form HH:MM:SS-HH:MM:SS", raw))?;
+
+    let start_secs = parse_timestamp(start)?;
+    let end_secs = parse_timestamp(end)?;
+    if start_secs >= end_secs {
+        bail!("Section start ({}) must be before end ({})", start, end);
+    }
+
+    Ok(format!("*{}-{}", start, end))
+}
+
+fn validate_audio_format(fmt: &str) -> Result<()> {
+    if AUDIO_CODECS.contains(&fmt) {
+        Ok(())
+    } else {
+        bail!("Unsupported audio format '{}'; expected one of {:?}", fmt, AUDIO_CODECS);
+    }
+}
+
+/// Ask whether to clip the download to a time range, reusing `--section` if one
+/// was already supplied on the command line.
+fn prompt_section(section_override: &Option<String>) -> Result<Option<String>> {
+    if let Some(s) = section_override {
+        return Ok(Some(s.clone()));
+    }
+
+    if Confirm::new()
+        .with_prompt("Download only a specific time range?")
+        .default(false)
+        .interact()?
+    {
+        let start: String = Input::new().with_prompt("Start time (HH:MM:SS)").interact()?;
+        let end: String = Input::new().with_prompt("End time (HH:MM:SS)").interact()?;
+        Ok(Some(parse_section(&format!("{}-{}", start, end))?))
+    } else {
+        Ok(None)
     }
-    
-    formats
 }
 
 fn get_download_directory(cli_dir: Option<PathBuf>) -> Result<PathBuf> {
@@ -134,13 +481,13 @@ fn get_download_directory(cli_dir: Option<PathBuf>) -> Result<PathBuf> {
                 "Videos directory (~/Videos)".to_string(),
                 "Custom path (enter manually)".to_string(),
             ];
-            
+
             let selection = Select::new()
                 .with_prompt("Select download directory")
                 .items(&options)
                 .default(0)
                 .interact()?;
-                
+
             match selection {
                 0 => Ok(PathBuf::from(".")),
                 1 => Ok(dirs::download_dir().unwrap_or_else(|| PathBuf::from("./Downloads"))),
@@ -159,83 +506,178 @@ fn get_download_directory(cli_dir: Option<PathBuf>) -> Result<PathBuf> {
     }
 }
 
-async fn download_media(url: &str, output_dir: &PathBuf, verbose: bool) -> Result<()> {
-    ensure_yt_dlp().await?;
+/// CLI-derived options threaded through `download_media`, mirroring
+/// `NetworkOptions` so the call site doesn't grow a positional parameter
+/// every time a new flag is added.
+struct DownloadOptions {
+    subs: Option<String>,
+    section: Option<String>,
+    audio_format: Option<String>,
+    audio_quality: Option<String>,
+    playlist_items: Option<String>,
+    no_playlist: bool,
+    net: NetworkOptions,
+    bootstrap: bool,
+}
 
-    // First, list available formats
-    let mut list_formats = Command::new("yt-dlp");
-    list_formats
-        .arg(url)
-        .arg("-F")
-        .arg("--no-check-certificates")
-        .arg("--force-ipv4");
+impl From<Cli> for DownloadOptions {
+    fn from(cli: Cli) -> Self {
+        Self {
+            net: NetworkOptions::from(&cli),
+            subs: cli.subs,
+            section: cli.section,
+            audio_format: cli.audio_format,
+            audio_quality: cli.audio_quality,
+            playlist_items: cli.playlist_items,
+            no_playlist: cli.no_playlist,
+            bootstrap: cli.bootstrap,
+        }
+    }
+}
+
+async fn download_media(
+    url: &str,
+    output_dir: &PathBuf,
+    verbose: bool,
+    opts: DownloadOptions,
+) -> Result<()> {
+    let DownloadOptions {
+        subs,
+        section,
+        audio_format,
+        audio_quality,
+        playlist_items,
+        no_playlist,
+        net,
+        bootstrap,
+    } = opts;
+
+    let yt_dlp = ensure_yt_dlp(bootstrap).await?;
 
     println!("{}", "Checking available formats...".bold().green());
-    let formats = list_formats.output().context("Failed to list formats")?;
-    let formats_str = String::from_utf8_lossy(&formats.stdout);
-    
+    // List with `--flat-playlist` first: for a playlist URL this returns every
+    // entry's id/title/duration without yt-dlp extracting each one's formats and
+    // subtitles, which on a large playlist would mean many slow round-trips just
+    // to render the selection menu below.
+    let info = fetch_video_info(&yt_dlp, url, no_playlist, true, &net)?;
+    let is_playlist = !no_playlist && info.is_playlist();
+
+    let playlist_items_spec = if is_playlist {
+        match &playlist_items {
+            Some(spec) => Some(spec.clone()),
+            None => {
+                println!("\n{}", format!("Playlist detected ({} entries)", info.entries.len()).bold().cyan());
+                Some(select_playlist_items(&info.entries)?)
+            }
+        }
+    } else {
+        None
+    };
+
+    // The flat listing carries no per-entry `formats`/`subtitles`; fetch those in
+    // full, but only for the first entry, which stands in for the quality and
+    // subtitle prompts below.
+    let representative_info: Option<VideoInfo> = if is_playlist {
+        match info.entries.first().and_then(|e| e.url.as_deref()) {
+            Some(entry_url) => Some(fetch_video_info(&yt_dlp, entry_url, true, false, &net)?),
+            None => None,
+        }
+    } else {
+        None
+    };
+
+    let format_source: &[Format] = if let Some(rep) = &representative_info {
+        &rep.formats
+    } else if is_playlist {
+        &[]
+    } else {
+        &info.formats
+    };
+
+    let empty_map = BTreeMap::new();
+    let (subtitles_source, auto_captions_source) = if let Some(rep) = &representative_info {
+        (&rep.subtitles, &rep.automatic_captions)
+    } else if is_playlist {
+        (&empty_map, &empty_map)
+    } else {
+        (&info.subtitles, &info.automatic_captions)
+    };
+
     if verbose {
-        // Print raw format information in verbose mode
         println!("\n{}", "Available formats (raw):".bold().cyan());
-        println!("{}", formats_str);
+        println!("{:#?}", format_source);
     }
-    
-    // Parse available formats
-    let parsed_formats = parse_available_formats(&formats_str);
-    
+
     // Group video formats by resolution for display
-    let mut video_resolutions: BTreeMap<Option<u32>, Vec<&FormatOption>> = BTreeMap::new();
-    let mut audio_formats: Vec<&FormatOption> = Vec::new();
-    
-    for format in &parsed_formats {
-        if format.is_video && !format.is_audio {
-            video_resolutions.entry(format.resolution).or_default().push(format);
-        } else if format.is_audio && !format.is_video {
+    let mut video_resolutions: BTreeMap<Option<u32>, Vec<&Format>> = BTreeMap::new();
+    let mut audio_formats: Vec<&Format> = Vec::new();
+
+    for format in format_source {
+        // Muxed/progressive formats carry both a vcodec and an acodec; they're
+        // usable as either a video candidate or an audio fallback, so they go
+        // into both buckets instead of being dropped by an either/or check.
+        if format.is_video() {
+            video_resolutions.entry(format.resolution()).or_default().push(format);
+        }
+        if format.is_audio() {
             audio_formats.push(format);
         }
     }
-    
-    // First ask if user wants video or audio
+
+    let subs_override: Option<Vec<String>> = subs.map(|s| {
+        s.split(',').map(|lang| lang.trim().to_string()).filter(|l| !l.is_empty()).collect()
+    });
+
+    let section_override: Option<String> = match section {
+        Some(s) => Some(parse_section(&s)?),
+        None => None,
+    };
+
+    if let Some(fmt) = &audio_format {
+        validate_audio_format(fmt)?;
+    }
+
+    // First ask if user wants video, audio, or subtitles
     let download_type = Select::new()
         .with_prompt("Select download type")
-        .items(&["Video", "Audio"])
+        .items(&["Video", "Audio", "Subtitles"])
         .default(0)
         .interact()?;
 
     let download_type = match download_type {
         0 => DownloadType::Video,
-        _ => DownloadType::Audio,
+        1 => DownloadType::Audio,
+        _ => DownloadType::Subtitle,
     };
-    
-    let output_template = match download_type {
-        DownloadType::Video => format!("{}/%(title)s_%(height)sp.%(ext)s", output_dir.display()),
-        DownloadType::Audio => format!("{}/%(title)s.%(ext)s", output_dir.display()),
+
+    let output_template = match (&download_type, is_playlist) {
+        (DownloadType::Video, true) => format!("{}/%(playlist_index)s - %(title)s_%(height)sp.%(ext)s", output_dir.display()),
+        (DownloadType::Video, false) => format!("{}/%(title)s_%(height)sp.%(ext)s", output_dir.display()),
+        (DownloadType::Audio, true) => format!("{}/%(playlist_index)s - %(title)s.%(ext)s", output_dir.display()),
+        (DownloadType::Audio, false) => format!("{}/%(title)s.%(ext)s", output_dir.display()),
+        (DownloadType::Subtitle, true) => format!("{}/%(playlist_index)s - %(title)s.%(ext)s", output_dir.display()),
+        (DownloadType::Subtitle, false) => format!("{}/%(title)s.%(ext)s", output_dir.display()),
     };
-    
+
     match download_type {
         DownloadType::Video => {
             println!("\n{}", "Available Video Resolutions (MP4 only):".bold().cyan());
-            
+
             // First, get the best audio format (prefer m4a for mp4 compatibility)
             let best_audio = audio_formats.iter()
-                .find(|f| f.extension == "m4a")
+                .find(|f| f.ext == "m4a")
                 .or_else(|| audio_formats.first())
-                .unwrap_or_else(|| panic!("No audio formats found"));
+                .ok_or_else(|| anyhow::anyhow!("No audio formats found"))?;
 
             // Collect only MP4 video formats by resolution
-            let mut mp4_formats: Vec<(u32, FormatOption)> = video_resolutions.iter()
+            let mut mp4_formats: Vec<(u32, Format)> = video_resolutions.iter()
                 .filter_map(|(res, formats)| {
                     res.map(|resolution| {
                         // Find best MP4 format for this resolution
                         let best_format = formats.iter()
-                            .filter(|f| f.is_video && f.extension == "mp4")
-                            .max_by_key(|f| {
-                                // Prefer formats with higher bitrate (typically better quality)
-                                f.format_description
-                                    .split_whitespace()
-                                    .find(|w| w.ends_with('k'))
-                                    .and_then(|w| w.trim_end_matches('k').parse::<u32>().ok())
-                                    .unwrap_or(0)
+                            .filter(|f| f.is_video() && f.ext == "mp4")
+                            .max_by(|a, b| {
+                                a.tbr.unwrap_or(0.0).partial_cmp(&b.tbr.unwrap_or(0.0)).unwrap()
                             })
                             .map(|f| (*f).clone());
                         (resolution, best_format)
@@ -255,130 +697,192 @@ async fn download_media(url: &str, output_dir: &PathBuf, verbose: bool) -> Resul
 
             // Create quality options
             let mut quality_options = Vec::new();
-            
+
             // Display available resolutions
             println!("\nSelect video quality (will be combined with best audio):");
             for (i, (resolution, format)) in mp4_formats.iter().enumerate() {
-                let size_info = format.filesize.as_ref()
-                    .map(|s| format!(" (~{})", s))
+                let size_info = format.filesize()
+                    .map(|s| format!(" (~{})", human_size(s)))
                     .unwrap_or_default();
-                
+
                 let quality_str = format!("{}p MP4{}", resolution, size_info);
-                quality_options.push((quality_str.clone(), format.id.clone(), *resolution));
-                
-                println!("  {}. {} ({})", 
-                    (i + 1).to_string().bold(), 
+                quality_options.push((quality_str.clone(), format.format_id.clone(), *resolution));
+
+                println!("  {}. {} ({})",
+                    (i + 1).to_string().bold(),
                     quality_str.bold().green(),
-                    format.format_description.bright_black()
+                    format.description().bright_black()
                 );
             }
-            
+
             // Let user select quality
             let selected_idx = Select::new()
                 .with_prompt("Select video quality")
                 .items(&quality_options.iter().map(|(q, _, _)| q.clone()).collect::<Vec<_>>())
                 .default(0)
                 .interact()?;
-                
+
             // Get selected format
-            let (quality_str, video_id, _) = &quality_options[selected_idx];
-            
-            // Combine with best audio
-            let format_arg = format!("{}+{}", video_id, best_audio.id);
-            
+            let (quality_str, video_id, resolution) = &quality_options[selected_idx];
+
+            // Combine with best audio. In playlist mode, format ids are specific to
+            // the representative entry and won't resolve on the others, so fall back
+            // to a resolution-bounded selector that yt-dlp can apply to every entry.
+            let format_arg = if is_playlist {
+                format!("bestvideo[height<={}]+bestaudio/best[height<={}]", resolution, resolution)
+            } else {
+                format!("{}+{}", video_id, best_audio.format_id)
+            };
+
+            // Offer to embed subtitles alongside the video, mirroring the ctrl+s
+            // behavior of the mpv youtube-download script
+            let embed_langs = if let Some(langs) = &subs_override {
+                langs.clone()
+            } else if subtitles_source.is_empty() && auto_captions_source.is_empty() {
+                Vec::new()
+            } else if Confirm::new()
+                .with_prompt("Embed subtitles into the video?")
+                .default(false)
+                .interact()?
+            {
+                select_subtitle_langs(subtitles_source, auto_captions_source, "Select subtitle language(s) to embed")?
+            } else {
+                Vec::new()
+            };
+
+            let section_arg = prompt_section(&section_override)?;
+
             println!("\n{}", "Starting download...".bold().green());
             println!("Quality: {}", quality_str.bold());
             println!("Video format: {} ({})", video_id.bold().yellow(), "MP4".bright_blue());
-            println!("Audio format: {} ({})", best_audio.id.bold().yellow(), best_audio.format_description);
+            println!("Audio format: {} ({})", best_audio.format_id.bold().yellow(), best_audio.description());
             println!("Download location: {}", output_dir.display().to_string().bold());
+            if !embed_langs.is_empty() {
+                println!("Subtitles: {}", embed_langs.join(",").bold());
+            }
+            if let Some(section) = &section_arg {
+                println!("Section: {}", section.bold());
+            }
 
             // Configure download command
-            let mut command = Command::new("yt-dlp");
+            let mut command = base_command(&yt_dlp, url, &net);
             command
-                .arg(url)
                 .arg("-f").arg(&format_arg)
                 .arg("-o").arg(&output_template)
                 .arg("--progress")
-                .arg("--no-check-certificates")
-                .arg("--force-ipv4")
-                .arg("--geo-bypass")
-                .arg("--no-playlist")
                 .arg("--merge-output-format").arg("mp4")
                 .arg("--prefer-ffmpeg");
-                
+
+            if let Some(spec) = &playlist_items_spec {
+                command.arg("--playlist-items").arg(spec);
+            } else {
+                command.arg("--no-playlist");
+            }
+
+            if !embed_langs.is_empty() {
+                command
+                    .arg("--embed-subs")
+                    .arg("--write-auto-subs")
+                    .arg("--sub-langs").arg(embed_langs.join(","));
+            }
+
+            if let Some(section) = &section_arg {
+                command
+                    .arg("--download-sections").arg(section)
+                    .arg("--force-keyframes-at-cuts");
+            }
+
             if verbose {
                 println!("\n{}", "Running command:".bold().cyan());
                 println!("{:?}", command);
             }
-            
+
             let status = command.status().context("Failed to execute yt-dlp")?;
-            
+
             if !status.success() {
                 println!("{}", "Download failed with primary format. Retrying with alternative method...".bold().yellow());
-                
+
                 // Fallback with simpler options
-                let mut retry_command = Command::new("yt-dlp");
+                let mut retry_command = base_command(&yt_dlp, url, &net);
                 retry_command
-                    .arg(url)
                     .arg("-f").arg("bestvideo+bestaudio/best")
                     .arg("-o").arg(&output_template)
-                    .arg("--force-ipv4")
-                    .arg("--no-check-certificates")
                     .arg("--merge-output-format").arg("mp4")
                     .arg("--prefer-ffmpeg");
-                
+
+                if let Some(spec) = &playlist_items_spec {
+                    retry_command.arg("--playlist-items").arg(spec);
+                } else {
+                    retry_command.arg("--no-playlist");
+                }
+
+                if !embed_langs.is_empty() {
+                    retry_command
+                        .arg("--embed-subs")
+                        .arg("--write-auto-subs")
+                        .arg("--sub-langs").arg(embed_langs.join(","));
+                }
+
+                if let Some(section) = &section_arg {
+                    retry_command
+                        .arg("--download-sections").arg(section)
+                        .arg("--force-keyframes-at-cuts");
+                }
+
                 let retry_status = retry_command.status().context("Failed to execute retry download")?;
-                
+
                 if !retry_status.success() {
                     bail!("Download failed after retry. Please try a different format or URL.");
                 }
             }
-            
+
             println!("{}", "Download completed successfully!".bold().green());
             println!("File saved to: {}", output_dir.display().to_string().bold());
         },
         DownloadType::Audio => {
             println!("\n{}", "Available Audio Formats:".bold().cyan());
-            
+
             // Show only top 5 audio formats (sort by likely quality)
-            let top_audio_formats: Vec<&FormatOption> = audio_formats.iter()
+            let top_audio_formats: Vec<&Format> = audio_formats.iter()
                 .take(5)
                 .copied()
                 .collect();
-            
+
             if top_audio_formats.len() < audio_formats.len() {
                 println!("{}", "Showing only top 5 audio formats. Use verbose mode (-v) to see all.".yellow());
             }
-            
+
             let mut audio_options = Vec::new();
             for (i, format) in top_audio_formats.iter().enumerate() {
-                let size_info = format.filesize.as_ref()
-                    .map(|s| format!(" ({})", s))
+                let size_info = format.filesize()
+                    .map(|s| format!(" ({})", human_size(s)))
                     .unwrap_or_default();
-                    
-                let option_str = format!("{} - {}{}", format.id, format.extension, size_info);
+
+                let option_str = format!("{} - {}{}", format.format_id, format.ext, size_info);
                 audio_options.push(option_str);
-                
-                println!("  {}. {}", i+1, format.format_description);
+
+                println!("  {}. {}", i+1, format.description());
             }
-            
+
             // Add option for best audio
             audio_options.push("Best audio (automatic selection)".to_string());
             audio_options.push("Custom format (enter format ID directly)".to_string());
-            
+
             let selected_idx = Select::new()
                 .with_prompt("Select audio format")
                 .items(&audio_options)
                 .default(audio_options.len() - 2) // Default to "Best audio"
                 .interact()?;
-                
+
             let format_arg = if selected_idx < top_audio_formats.len() {
-                // User selected a specific audio format
+                // User selected a specific audio format. In playlist mode this id is
+                // only valid for the representative entry, so "bestaudio" is used
+                // for every entry in the batch instead.
                 let format = top_audio_formats[selected_idx];
-                println!("Selected audio format: {} ({})", 
-                    format.id.bold().green(),
-                    format.format_description);
-                format.id.clone()
+                println!("Selected audio format: {} ({})",
+                    format.format_id.bold().green(),
+                    format.description());
+                if is_playlist { "bestaudio".to_string() } else { format.format_id.clone() }
             } else if selected_idx == top_audio_formats.len() {
                 // Best audio option
                 println!("Selected: {}", "Best audio (automatic)".bold().green());
@@ -390,59 +894,165 @@ async fn download_media(url: &str, output_dir: &PathBuf, verbose: bool) -> Resul
                     .default("bestaudio".into())
                     .interact()?
             };
-            
+
+            let codec = match &audio_format {
+                Some(fmt) => fmt.clone(),
+                None => {
+                    let idx = Select::new()
+                        .with_prompt("Select audio codec")
+                        .items(&AUDIO_CODECS)
+                        .default(3) // mp3
+                        .interact()?;
+                    AUDIO_CODECS[idx].to_string()
+                }
+            };
+
+            let quality = match &audio_quality {
+                Some(q) => q.clone(),
+                None => Input::<String>::new()
+                    .with_prompt("Audio quality (0-9 VBR, or a bitrate like 128K)")
+                    .default("5".into())
+                    .interact()?,
+            };
+
+            let section_arg = prompt_section(&section_override)?;
+
             println!("\n{}", "Starting audio download...".bold().green());
             println!("Format specification: {}", format_arg.bold());
+            println!("Audio codec: {}", codec.bold());
             println!("Download location: {}", output_dir.display().to_string().bold());
-            
-            let mut command = Command::new("yt-dlp");
+            if let Some(section) = &section_arg {
+                println!("Section: {}", section.bold());
+            }
+
+            let mut command = base_command(&yt_dlp, url, &net);
             command
-                .arg(url)
                 .arg("-f").arg(&format_arg)
                 .arg("-o").arg(&output_template)
-                .arg("--progress")
-                .arg("--no-check-certificates")
-                .arg("--force-ipv4")
-                .arg("--geo-bypass")
-                .arg("--no-playlist")
-                .arg("-x") // Extract audio
-                .arg("--audio-format").arg("mp3") // Convert to mp3
-                .arg("--prefer-ffmpeg");
-                
+                .arg("--progress");
+
+            if let Some(spec) = &playlist_items_spec {
+                command.arg("--playlist-items").arg(spec);
+            } else {
+                command.arg("--no-playlist");
+            }
+
+            if codec == "best" {
+                // Best already picks a native container; skip -x to avoid a needless transcode
+                command.arg("--prefer-ffmpeg");
+            } else {
+                command
+                    .arg("-x") // Extract audio
+                    .arg("--audio-format").arg(&codec)
+                    .arg("--audio-quality").arg(&quality)
+                    .arg("--prefer-ffmpeg");
+            }
+
+            if let Some(section) = &section_arg {
+                command
+                    .arg("--download-sections").arg(section)
+                    .arg("--force-keyframes-at-cuts");
+            }
+
             if verbose {
                 println!("\n{}", "Running command:".bold().cyan());
                 println!("{:?}", command);
             }
-            
+
             let status = command.status().context("Failed to execute yt-dlp")?;
-            
+
             if !status.success() {
                 println!("{}", "Download failed with primary format. Retrying with alternative method...".bold().yellow());
-                
+
                 // Fallback with simpler options
-                let mut retry_command = Command::new("yt-dlp");
+                let mut retry_command = base_command(&yt_dlp, url, &net);
                 retry_command
-                    .arg(url)
                     .arg("-f").arg("bestaudio")
-                    .arg("-o").arg(&output_template)
-                    .arg("--force-ipv4")
-                    .arg("--no-check-certificates")
-                    .arg("-x")
-                    .arg("--audio-format").arg("mp3")
-                    .arg("--prefer-ffmpeg");
-                
+                    .arg("-o").arg(&output_template);
+
+                if codec != "best" {
+                    retry_command
+                        .arg("-x")
+                        .arg("--audio-format").arg(&codec)
+                        .arg("--audio-quality").arg(&quality);
+                }
+                retry_command.arg("--prefer-ffmpeg");
+
+                if let Some(spec) = &playlist_items_spec {
+                    retry_command.arg("--playlist-items").arg(spec);
+                } else {
+                    retry_command.arg("--no-playlist");
+                }
+
+                if let Some(section) = &section_arg {
+                    retry_command
+                        .arg("--download-sections").arg(section)
+                        .arg("--force-keyframes-at-cuts");
+                }
+
                 let retry_status = retry_command.status().context("Failed to execute retry download")?;
-                
+
                 if !retry_status.success() {
                     bail!("Audio download failed after retry. Please try a different format or URL.");
                 }
             }
-            
+
             println!("{}", "Audio download completed successfully!".bold().green());
             println!("File saved to: {}", output_dir.display().to_string().bold());
+        },
+        DownloadType::Subtitle => {
+            let langs = if let Some(langs) = subs_override {
+                langs
+            } else {
+                select_subtitle_langs(subtitles_source, auto_captions_source, "Select subtitle language(s) to download")?
+            };
+
+            if langs.is_empty() {
+                bail!("No subtitle languages selected");
+            }
+
+            // A picked lang may only exist as an auto-generated caption; `--write-subs`
+            // alone never fetches those, so also pass `--write-auto-subs` when one was chosen.
+            let has_auto = langs.iter().any(|l| auto_captions_source.contains_key(l));
+
+            println!("\n{}", "Starting subtitle download...".bold().green());
+            println!("Languages: {}", langs.join(",").bold());
+            println!("Download location: {}", output_dir.display().to_string().bold());
+
+            let mut command = base_command(&yt_dlp, url, &net);
+            command
+                .arg("--write-subs")
+                .arg("--sub-langs").arg(langs.join(","))
+                .arg("--skip-download")
+                .arg("--convert-subs").arg("srt")
+                .arg("-o").arg(&output_template);
+
+            if has_auto {
+                command.arg("--write-auto-subs");
+            }
+
+            if let Some(spec) = &playlist_items_spec {
+                command.arg("--playlist-items").arg(spec);
+            } else {
+                command.arg("--no-playlist");
+            }
+
+            if verbose {
+                println!("\n{}", "Running command:".bold().cyan());
+                println!("{:?}", command);
+            }
+
+            let status = command.status().context("Failed to execute yt-dlp")?;
+
+            if !status.success() {
+                bail!("Subtitle download failed. Please try a different language or URL.");
+            }
+
+            println!("{}", "Subtitle download completed successfully!".bold().green());
+            println!("File saved to: {}", output_dir.display().to_string().bold());
         }
     }
-    
+
     Ok(())
 }
 
@@ -460,18 +1070,26 @@ async fn main() -> Result<()> {
             .with_prompt("Enter the URL to download")
             .interact()?
     };
-    
-    // Get download directory (from CLI or manual selection)
+
+    // Captured before `cli` is consumed below.
+    let verbose = cli.verbose;
+
+    // Get download directory (from CLI or manual selection). This moves
+    // `cli.output_dir` out, so the remaining fields are split off into `rest`
+    // first; `cli` itself can no longer be consumed wholesale afterwards.
+    let rest = Cli { output_dir: None, ..cli };
     let output_dir = get_download_directory(cli.output_dir)?;
 
     // Create output directory if it doesn't exist
     tokio::fs::create_dir_all(&output_dir).await
         .with_context(|| format!("Failed to create output directory: {}", output_dir.display()))?;
-    
+
     println!("Download directory: {}", output_dir.display().to_string().bold());
 
     // Start the download process
-    download_media(&url, &output_dir, cli.verbose).await?;
+    let opts = DownloadOptions::from(rest);
+
+    download_media(&url, &output_dir, verbose, opts).await?;
 
     Ok(())
-}
\ No newline at end of file
+}